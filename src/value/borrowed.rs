@@ -1,18 +1,17 @@
 ///A dom object that references the raw input data to avoid allocations
 /// it trades having lifetimes for a gain in performance.
+mod binary;
 mod cmp;
 mod from;
+mod scan;
 mod serialize;
 
+pub use self::binary::{from_binary, to_binary, to_binary_with};
+
 use crate::number::Number;
-use crate::portability::trailingzeroes;
 use crate::value::{ValueTrait, ValueType};
-use crate::{static_cast_u32, stry, unlikely, Deserializer, ErrorType, Result};
+use crate::{stry, unlikely, Deserializer, ErrorType, Result};
 use halfbrown::HashMap;
-#[cfg(target_arch = "x86")]
-use std::arch::x86::*;
-#[cfg(target_arch = "x86_64")]
-use std::arch::x86_64::*;
 use std::borrow::{Borrow, Cow};
 use std::fmt;
 use std::mem;
@@ -342,57 +341,24 @@ impl<'de> Deserializer<'de> {
     // fancy in it like object keys
     #[cfg_attr(not(feature = "no-inline"), inline(always))]
     fn parse_small_str_(&mut self) -> Result<Value<'de>> {
-        let mut res = SmallString {
-            len: 0,
-            data: unsafe { mem::uninitialized() },
-        };
         let idx = self.iidx + 1;
         let src: &[u8] = unsafe { &self.input.get_unchecked(idx..) };
 
-        //short strings are very common for IDs
-        unsafe {
-            res.data
-                .get_unchecked_mut(..32)
-                .clone_from_slice(src.get_unchecked(..32));
-        };
-        #[allow(clippy::cast_ptr_alignment)]
-        let v: __m256i = unsafe { _mm256_loadu_si256(src.as_ptr() as *const __m256i) };
-        let bs_bits: u32 = unsafe {
-            static_cast_u32!(_mm256_movemask_epi8(_mm256_cmpeq_epi8(
-                v,
-                _mm256_set1_epi8(b'\\' as i8)
-            )))
-        };
-        let quote_mask = unsafe { _mm256_cmpeq_epi8(v, _mm256_set1_epi8(b'"' as i8)) };
-        let quote_bits = unsafe { static_cast_u32!(_mm256_movemask_epi8(quote_mask)) };
-        if (bs_bits.wrapping_sub(1) & quote_bits) != 0 {
-            let quote_dist: u8 = trailingzeroes(u64::from(quote_bits)) as u8;
-            res.len = quote_dist;
-            return Ok(Value::SmallString(res));
-        } else if (quote_bits.wrapping_sub(1) & bs_bits) == 0 {
-            // Nothing bad so far we can do another 22 characters
-            unsafe {
-                res.data
-                    .get_unchecked_mut(32..=SMALL_STR_LEN)
-                    .clone_from_slice(src.get_unchecked(32..=SMALL_STR_LEN));
-            };
-            #[allow(clippy::cast_ptr_alignment)]
-            let v: __m256i =
-                unsafe { _mm256_loadu_si256(src.get_unchecked(32..).as_ptr() as *const __m256i) };
-            let bs_bits: u32 = unsafe {
-                static_cast_u32!(_mm256_movemask_epi8(_mm256_cmpeq_epi8(
-                    v,
-                    _mm256_set1_epi8(b'\\' as i8)
-                )))
-            };
-            let quote_mask = unsafe { _mm256_cmpeq_epi8(v, _mm256_set1_epi8(b'"' as i8)) };
-            let quote_bits = unsafe { static_cast_u32!(_mm256_movemask_epi8(quote_mask)) };
-            if (bs_bits.wrapping_sub(1) & quote_bits) != 0 {
-                let quote_dist: u8 = trailingzeroes(u64::from(quote_bits)) as u8;
-                if quote_dist <= 22 {
-                    res.len = quote_dist + 32;
-                    return Ok(Value::SmallString(res));
+        // The backend is chosen on first use based on the CPU we run on,
+        // keeping the AVX2 fast path while still working on SSE4.2, NEON and
+        // targets with no SIMD at all.
+        if let Some(len) = unsafe { scan::first_unescaped_quote(src) } {
+            if len <= SMALL_STR_LEN {
+                let mut res = SmallString {
+                    len: len as u8,
+                    data: unsafe { mem::uninitialized() },
+                };
+                unsafe {
+                    res.data
+                        .get_unchecked_mut(..len)
+                        .clone_from_slice(src.get_unchecked(..len));
                 }
+                return Ok(Value::SmallString(res));
             }
         }
         self.parse_str_().map(Value::from)
@@ -0,0 +1,737 @@
+/// A compact binary codec for the borrowed [`Value`] DOM.
+///
+/// Re-serializing to JSON is wasteful when all we want is to move a parsed
+/// tree around: punctuation, whitespace and repeated key bytes all come back.
+/// Instead we borrow the structure-of-streams idea from compact binary
+/// serializers. A pre-order traversal of the tree writes into four independent
+/// byte streams:
+///
+/// * a *tag* stream with one byte per node describing its kind (and the value
+///   of booleans in a spare bit, so they cost no payload);
+/// * a *length* stream holding array/object/string lengths as LEB128 varints;
+/// * a *number* stream holding encoded numeric payloads;
+/// * a *bytes* stream holding concatenated string and key UTF-8.
+///
+/// The streams are concatenated after a small header recording each one's
+/// length. Decoding walks the tags in order, pulling lengths and payloads from
+/// their own cursors and recursing `length` times for arrays and objects.
+/// Strings longer than the inline budget and all object keys borrow directly
+/// out of the input, matching the zero-copy philosophy of
+/// [`to_value`](super::to_value); shorter strings are copied into an inline
+/// [`SmallString`], the form `to_value` gives them.
+///
+/// Documents that are arrays of similarly-shaped objects repeat the same keys
+/// over and over. [`to_binary_with`] can intern every object key into a
+/// dictionary emitted once as a fifth stream, replacing each occurrence with a
+/// varint index. The dictionary keys stay contiguous so the decoder hands out
+/// the same borrowed slice for every repeat of a key — the reuse
+/// `insert_nocheck` wants — rather than front-coding the table, which would
+/// force owned reconstruction and defeat the zero-copy borrow. The mode only
+/// pays off on repetitive input, so [`to_binary_with`] measures the break-even
+/// and silently falls back to inline keys when a dictionary would be larger.
+use super::{Map, SmallString, Value, SMALL_STR_LEN};
+use crate::number::Number;
+use crate::{Error, ErrorType, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Magic prefix so we can reject obviously-unrelated input early.
+const MAGIC: [u8; 4] = *b"SJB1";
+
+/// Flag bit set when the payload carries an object-key dictionary stream.
+const FLAG_KEY_DICT: u8 = 0b0000_0001;
+
+// Node kinds, stored in the low three bits of a tag byte.
+const TAG_NULL: u8 = 0;
+const TAG_BOOL: u8 = 1;
+const TAG_NUMBER: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_ARRAY: u8 = 4;
+const TAG_OBJECT: u8 = 5;
+
+/// Bit in the tag byte carrying a boolean's value.
+const BOOL_BIT: u8 = 0b0000_1000;
+
+// Number stream kind prefixes.
+const NUM_I64: u8 = 0;
+const NUM_U64: u8 = 1;
+const NUM_F32: u8 = 2;
+const NUM_F64: u8 = 3;
+
+/// Serialize a borrowed `Value` into the compact binary representation with
+/// inline object keys.
+pub fn to_binary(value: &Value) -> Vec<u8> {
+    to_binary_with(value, false)
+}
+
+/// Serialize a borrowed `Value`, optionally interning object keys into a
+/// dictionary.
+///
+/// With `dictionary == true` the encoder collects every object key, and — only
+/// when the interned table plus its varint indices come out smaller than the
+/// repeated inline keys would — emits the dictionary once and references each
+/// key by index. For small or key-diverse documents it transparently falls
+/// back to inline keys, so passing `true` never makes the output larger.
+pub fn to_binary_with(value: &Value, dictionary: bool) -> Vec<u8> {
+    let dict = if dictionary {
+        Dictionary::build(value)
+    } else {
+        None
+    };
+    let mut enc = Encoder::new(dict);
+    enc.write_value(value);
+    enc.finish()
+}
+
+/// Reconstruct a `Value` from bytes produced by [`to_binary`].
+///
+/// Object keys and strings longer than `SMALL_STR_LEN` borrow out of `input`,
+/// hence the shared `'v` lifetime on the returned tree; shorter strings are
+/// copied into a `SmallString`.
+pub fn from_binary<'v>(input: &'v [u8]) -> Result<Value<'v>> {
+    let mut dec = Decoder::new(input)?;
+    let value = dec.read_value()?;
+    Ok(value)
+}
+
+struct Encoder<'a> {
+    tags: Vec<u8>,
+    lens: Vec<u8>,
+    nums: Vec<u8>,
+    bytes: Vec<u8>,
+    dict: Option<Dictionary<'a>>,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(dict: Option<Dictionary<'a>>) -> Self {
+        Encoder {
+            tags: Vec::new(),
+            lens: Vec::new(),
+            nums: Vec::new(),
+            bytes: Vec::new(),
+            dict,
+        }
+    }
+
+    fn write_value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.tags.push(TAG_NULL),
+            Value::Bool(b) => self
+                .tags
+                .push(TAG_BOOL | if *b { BOOL_BIT } else { 0 }),
+            Value::Number(n) => {
+                self.tags.push(TAG_NUMBER);
+                write_number(&mut self.nums, n);
+            }
+            Value::String(s) => self.write_str(s.as_bytes()),
+            Value::SmallString(s) => self.write_str(s.as_bytes()),
+            Value::Array(a) => {
+                self.tags.push(TAG_ARRAY);
+                write_varint(&mut self.lens, a.len() as u64);
+                for element in a {
+                    self.write_value(element);
+                }
+            }
+            Value::Object(m) => {
+                self.tags.push(TAG_OBJECT);
+                write_varint(&mut self.lens, m.len() as u64);
+                for (k, v) in m.iter() {
+                    // In dictionary mode the key is a varint dictionary index
+                    // written to the length stream; otherwise it is a
+                    // length-prefixed blob.
+                    match self.dict.as_ref().map(|d| d.index_of(k.as_bytes())) {
+                        Some(idx) => write_varint(&mut self.lens, u64::from(idx)),
+                        None => self.write_key(k.as_bytes()),
+                    }
+                    self.write_value(v);
+                }
+            }
+        }
+    }
+
+    fn write_str(&mut self, s: &[u8]) {
+        self.tags.push(TAG_STRING);
+        write_varint(&mut self.lens, s.len() as u64);
+        self.bytes.extend_from_slice(s);
+    }
+
+    /// Keys are always strings and carry no tag of their own; their length
+    /// goes into the length stream and their bytes into the bytes stream.
+    fn write_key(&mut self, k: &[u8]) {
+        write_varint(&mut self.lens, k.len() as u64);
+        self.bytes.extend_from_slice(k);
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let dict_stream = self.dict.as_ref().map(Dictionary::encode);
+        let dict_len = dict_stream.as_ref().map_or(0, Vec::len);
+        let mut out = Vec::with_capacity(
+            MAGIC.len()
+                + 1
+                + 25
+                + self.tags.len()
+                + self.lens.len()
+                + self.nums.len()
+                + self.bytes.len()
+                + dict_len,
+        );
+        out.extend_from_slice(&MAGIC);
+        out.push(if dict_stream.is_some() {
+            FLAG_KEY_DICT
+        } else {
+            0
+        });
+        write_varint(&mut out, self.tags.len() as u64);
+        write_varint(&mut out, self.lens.len() as u64);
+        write_varint(&mut out, self.nums.len() as u64);
+        write_varint(&mut out, self.bytes.len() as u64);
+        if dict_stream.is_some() {
+            write_varint(&mut out, dict_len as u64);
+        }
+        out.extend_from_slice(&self.tags);
+        out.extend_from_slice(&self.lens);
+        out.extend_from_slice(&self.nums);
+        out.extend_from_slice(&self.bytes);
+        if let Some(dict_stream) = dict_stream {
+            out.extend_from_slice(&dict_stream);
+        }
+        out
+    }
+}
+
+struct Decoder<'v> {
+    tags: Cursor<'v>,
+    lens: Cursor<'v>,
+    nums: Cursor<'v>,
+    bytes: Cursor<'v>,
+    dict: Option<Vec<&'v str>>,
+}
+
+impl<'v> Decoder<'v> {
+    fn new(input: &'v [u8]) -> Result<Self> {
+        let mut head = Cursor::new(input);
+        if head.take(MAGIC.len())? != MAGIC {
+            return Err(Error::generic(ErrorType::UnexpectedCharacter));
+        }
+        let flags = head.byte()?;
+        let tags_len = head.varint()? as usize;
+        let lens_len = head.varint()? as usize;
+        let nums_len = head.varint()? as usize;
+        let bytes_len = head.varint()? as usize;
+        let dict_len = if flags & FLAG_KEY_DICT != 0 {
+            head.varint()? as usize
+        } else {
+            0
+        };
+
+        let rest = head.rest();
+        // Sum the stream lengths with checked arithmetic: they come straight
+        // from untrusted input, so a header claiming `u64::MAX` must fail here
+        // rather than overflow the addition or panic in `split_at` below.
+        let total = tags_len
+            .checked_add(lens_len)
+            .and_then(|s| s.checked_add(nums_len))
+            .and_then(|s| s.checked_add(bytes_len))
+            .and_then(|s| s.checked_add(dict_len));
+        if total != Some(rest.len()) {
+            return Err(Error::generic(ErrorType::Eof));
+        }
+        let (tags, rest) = rest.split_at(tags_len);
+        let (lens, rest) = rest.split_at(lens_len);
+        let (nums, rest) = rest.split_at(nums_len);
+        let (bytes, dict_bytes) = rest.split_at(bytes_len);
+        let dict = if flags & FLAG_KEY_DICT != 0 {
+            Some(read_dictionary(dict_bytes)?)
+        } else {
+            None
+        };
+        Ok(Decoder {
+            tags: Cursor::new(tags),
+            lens: Cursor::new(lens),
+            nums: Cursor::new(nums),
+            bytes: Cursor::new(bytes),
+            dict,
+        })
+    }
+
+    fn read_value(&mut self) -> Result<Value<'v>> {
+        let tag = self.tags.byte()?;
+        match tag & 0b0000_0111 {
+            TAG_NULL => Ok(Value::Null),
+            TAG_BOOL => Ok(Value::Bool(tag & BOOL_BIT != 0)),
+            TAG_NUMBER => read_number(&mut self.nums).map(Value::Number),
+            TAG_STRING => {
+                // Mirror `to_value`: short strings come back inline so the
+                // round trip preserves the `SmallString` variant `PartialEq`
+                // distinguishes.
+                let s = self.read_str()?;
+                if s.len() <= SMALL_STR_LEN {
+                    Ok(Value::SmallString(small_string(s.as_bytes())))
+                } else {
+                    Ok(Value::String(Cow::Borrowed(s)))
+                }
+            }
+            TAG_ARRAY => {
+                let len = self.lens.varint()? as usize;
+                // `len` is attacker-controlled; each element needs at least a
+                // tag byte, so never reserve past the tags actually present.
+                let mut res = Vec::with_capacity(len.min(self.tags.remaining() + 1));
+                for _ in 0..len {
+                    res.push(self.read_value()?);
+                }
+                Ok(Value::Array(res))
+            }
+            TAG_OBJECT => {
+                let len = self.lens.varint()? as usize;
+                let mut res = Map::with_capacity(len.min(self.tags.remaining() + 1));
+                for _ in 0..len {
+                    let key = self.read_key()?;
+                    let value = self.read_value()?;
+                    res.insert_nocheck(Cow::Borrowed(key), value);
+                }
+                Ok(Value::Object(res))
+            }
+            _ => Err(Error::generic(ErrorType::UnexpectedCharacter)),
+        }
+    }
+
+    /// Read a length-prefixed string slice borrowed out of the bytes stream.
+    fn read_str(&mut self) -> Result<&'v str> {
+        let len = self.lens.varint()? as usize;
+        let raw = self.bytes.take(len)?;
+        std::str::from_utf8(raw).map_err(|_| Error::generic(ErrorType::InvalidUTF8))
+    }
+
+    /// Read an object key, either by dictionary index or inline, handing back
+    /// the shared dictionary slice so repeats of a key reuse one backing slice.
+    fn read_key(&mut self) -> Result<&'v str> {
+        match &self.dict {
+            Some(dict) => {
+                let idx = self.lens.varint()? as usize;
+                dict.get(idx)
+                    .copied()
+                    .ok_or_else(|| Error::generic(ErrorType::Eof))
+            }
+            None => self.read_str(),
+        }
+    }
+}
+
+/// Decode the dictionary stream into borrowed key slices in table order.
+fn read_dictionary(data: &[u8]) -> Result<Vec<&str>> {
+    let mut cur = Cursor::new(data);
+    let count = cur.varint()? as usize;
+    let mut keys = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = cur.varint()? as usize;
+        let raw = cur.take(len)?;
+        keys.push(std::str::from_utf8(raw).map_err(|_| Error::generic(ErrorType::InvalidUTF8))?);
+    }
+    Ok(keys)
+}
+
+/// Copy `raw` (known to be `<= SMALL_STR_LEN` bytes) into an inline
+/// [`SmallString`], the form `to_value` hands back for short strings.
+fn small_string(raw: &[u8]) -> SmallString {
+    let mut data = [0u8; SMALL_STR_LEN];
+    data[..raw.len()].copy_from_slice(raw);
+    SmallString {
+        data,
+        len: raw.len() as u8,
+    }
+}
+
+/// A forward-only reader over one stream.
+struct Cursor<'v> {
+    data: &'v [u8],
+    idx: usize,
+}
+
+impl<'v> Cursor<'v> {
+    fn new(data: &'v [u8]) -> Self {
+        Cursor { data, idx: 0 }
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let b = *self
+            .data
+            .get(self.idx)
+            .ok_or_else(|| Error::generic(ErrorType::Eof))?;
+        self.idx += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'v [u8]> {
+        let end = self
+            .idx
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| Error::generic(ErrorType::Eof))?;
+        let slice = &self.data[self.idx..end];
+        self.idx = end;
+        Ok(slice)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut result: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            result |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+            if shift >= 64 {
+                return Err(Error::generic(ErrorType::Eof));
+            }
+        }
+    }
+
+    fn rest(&self) -> &'v [u8] {
+        &self.data[self.idx..]
+    }
+
+    /// Bytes not yet consumed; an upper bound on how many more nodes this
+    /// stream can possibly describe.
+    fn remaining(&self) -> usize {
+        self.data.len() - self.idx
+    }
+}
+
+/// Append `value` to `out` as an unsigned LEB128 varint.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if value == 0 {
+            return;
+        }
+    }
+}
+
+/// Encode a `Number` into the number stream as compactly as it allows.
+///
+/// Integers dominate real documents (ids, counts, flags), so a signed integer
+/// gets a zig-zag LEB128 varint and an unsigned one a plain LEB128 varint,
+/// both collapsing small magnitudes to one or two bytes.
+/// Non-integers keep full precision but only pay for it when they have to: a
+/// value that survives a round trip through `f32` is stored in four bytes, and
+/// only the genuinely double-precision remainder falls back to eight.
+fn write_number(out: &mut Vec<u8>, n: &Number) {
+    // Match on the variant rather than the `as_i64`/`as_u64` accessors: a small
+    // `U64` also answers `as_i64`, and picking `I64` for it would silently
+    // change the variant on decode and break the round trip.
+    match n {
+        Number::I64(i) => {
+            out.push(NUM_I64);
+            write_varint(out, zigzag(*i));
+        }
+        Number::U64(u) => {
+            out.push(NUM_U64);
+            write_varint(out, *u);
+        }
+        Number::F64(f) => {
+            if f64::from(*f as f32) == *f {
+                out.push(NUM_F32);
+                out.extend_from_slice(&(*f as f32).to_bits().to_le_bytes());
+            } else {
+                out.push(NUM_F64);
+                out.extend_from_slice(&f.to_bits().to_le_bytes());
+            }
+        }
+    }
+}
+
+fn read_number(cur: &mut Cursor) -> Result<Number> {
+    match cur.byte()? {
+        NUM_I64 => Ok(Number::I64(unzigzag(cur.varint()?))),
+        NUM_U64 => Ok(Number::U64(cur.varint()?)),
+        NUM_F32 => {
+            let mut buf = [0u8; 4];
+            buf.copy_from_slice(cur.take(4)?);
+            Ok(Number::F64(f64::from(f32::from_bits(u32::from_le_bytes(
+                buf,
+            )))))
+        }
+        NUM_F64 => {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(cur.take(8)?);
+            Ok(Number::F64(f64::from_bits(u64::from_le_bytes(buf))))
+        }
+        _ => Err(Error::generic(ErrorType::UnexpectedCharacter)),
+    }
+}
+
+/// Map a signed integer onto an unsigned one so small magnitudes of either
+/// sign stay small under LEB128.
+#[inline]
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+#[inline]
+fn unzigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+/// Interned object keys, sorted and deduplicated, plus their lookup table.
+struct Dictionary<'a> {
+    keys: Vec<&'a [u8]>,
+    index: HashMap<&'a [u8], u32>,
+}
+
+impl<'a> Dictionary<'a> {
+    /// Build a dictionary for `value`, returning `None` when interning would
+    /// not shrink the output (few repeats, or a handful of keys).
+    fn build(value: &'a Value) -> Option<Self> {
+        let mut counts: HashMap<&'a [u8], u64> = HashMap::new();
+        collect_keys(value, &mut counts);
+        if counts.is_empty() {
+            return None;
+        }
+
+        let mut keys: Vec<&'a [u8]> = counts.keys().copied().collect();
+        keys.sort_unstable();
+        let index: HashMap<&'a [u8], u32> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, &k)| (k, i as u32))
+            .collect();
+
+        // Compare the bytes the two layouts would cost: inline repeats every
+        // key in full, the dictionary pays for the table once, the table's
+        // length in the header, and a varint index per occurrence.
+        let inline_cost: u64 = counts
+            .iter()
+            .map(|(k, c)| c * (varint_len(k.len() as u64) + k.len()) as u64)
+            .sum();
+        let table_cost: u64 = varint_len(keys.len() as u64) as u64
+            + keys
+                .iter()
+                .map(|k| (varint_len(k.len() as u64) + k.len()) as u64)
+                .sum::<u64>();
+        let index_cost: u64 = counts
+            .iter()
+            .map(|(k, c)| c * varint_len(u64::from(index[*k])) as u64)
+            .sum();
+
+        let header_cost = varint_len(table_cost) as u64;
+
+        if table_cost + header_cost + index_cost < inline_cost {
+            Some(Dictionary { keys, index })
+        } else {
+            None
+        }
+    }
+
+    fn index_of(&self, key: &[u8]) -> u32 {
+        self.index[key]
+    }
+
+    /// Encode the table as a count followed by length-prefixed keys, kept
+    /// contiguous so the decoder can borrow each key directly.
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_varint(&mut out, self.keys.len() as u64);
+        for key in &self.keys {
+            write_varint(&mut out, key.len() as u64);
+            out.extend_from_slice(key);
+        }
+        out
+    }
+}
+
+/// Tally how often each object key appears across the tree.
+fn collect_keys<'a>(value: &'a Value, counts: &mut HashMap<&'a [u8], u64>) {
+    match value {
+        Value::Array(a) => {
+            for element in a {
+                collect_keys(element, counts);
+            }
+        }
+        Value::Object(m) => {
+            for (k, v) in m.iter() {
+                *counts.entry(k.as_bytes()).or_insert(0) += 1;
+                collect_keys(v, counts);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Number of bytes `value` occupies as an unsigned LEB128 varint.
+fn varint_len(mut value: u64) -> usize {
+    let mut n = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        n += 1;
+    }
+    n
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::number::Number;
+    use std::borrow::Cow;
+
+    fn obj<'v>(pairs: Vec<(&'v str, Value<'v>)>) -> Value<'v> {
+        let mut m = Map::new();
+        for (k, v) in pairs {
+            m.insert(Cow::Borrowed(k), v);
+        }
+        Value::Object(m)
+    }
+
+    /// A tree touching every node kind, including a long string (`String`)
+    /// and a short one (`SmallString`) so the variant distinction is exercised.
+    fn sample<'v>() -> Value<'v> {
+        obj(vec![
+            ("id", Value::Number(Number::I64(7))),
+            ("null", Value::Null),
+            ("yes", Value::Bool(true)),
+            ("no", Value::Bool(false)),
+            ("small", Value::SmallString(small_string(b"short"))),
+            (
+                "long",
+                Value::String(Cow::Borrowed(
+                    "this string is comfortably longer than the inline small-string budget",
+                )),
+            ),
+            (
+                "nums",
+                Value::Array(vec![
+                    Value::Number(Number::I64(-1)),
+                    Value::Number(Number::F64(1.5)),
+                    Value::Number(Number::F64(0.1)),
+                ]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn round_trips_every_kind() {
+        let v = sample();
+        let bytes = to_binary(&v);
+        assert_eq!(from_binary(&bytes).unwrap(), v);
+    }
+
+    #[test]
+    fn short_strings_decode_as_small_string() {
+        // `to_value` emits `SmallString` for short strings; the codec must too,
+        // or `PartialEq` reports the round trip as unequal.
+        let v = Value::String(Cow::Borrowed("id42"));
+        let decoded = from_binary(&to_binary(&v)).unwrap();
+        assert_eq!(decoded, Value::SmallString(small_string(b"id42")));
+    }
+
+    #[test]
+    fn rejects_foreign_and_truncated_input() {
+        assert!(from_binary(b"not sjb").is_err());
+        let bytes = to_binary(&sample());
+        assert!(from_binary(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    fn number_round_trip(n: Number) -> Number {
+        let mut buf = Vec::new();
+        write_number(&mut buf, &n);
+        read_number(&mut Cursor::new(&buf)).unwrap()
+    }
+
+    #[test]
+    fn integers_round_trip_including_boundaries() {
+        for n in &[
+            Number::I64(0),
+            Number::I64(-1),
+            Number::I64(i64::min_value()),
+            Number::I64(i64::max_value()),
+            Number::U64(0),
+            Number::U64(7),
+            Number::U64(u64::max_value()),
+        ] {
+            assert_eq!(number_round_trip(n.clone()), n.clone());
+        }
+    }
+
+    #[test]
+    fn small_u64_keeps_its_variant() {
+        // A `U64` small enough to fit `i64` must not come back as `I64`.
+        assert_eq!(number_round_trip(Number::U64(7)), Number::U64(7));
+    }
+
+    #[test]
+    fn floats_shorten_to_f32_when_they_round_trip() {
+        let mut exact = Vec::new();
+        write_number(&mut exact, &Number::F64(1.5));
+        assert_eq!(exact[0], NUM_F32);
+        assert_eq!(exact.len(), 5);
+
+        let mut wide = Vec::new();
+        write_number(&mut wide, &Number::F64(0.1));
+        assert_eq!(wide[0], NUM_F64);
+        assert_eq!(wide.len(), 9);
+
+        assert_eq!(number_round_trip(Number::F64(1.5)), Number::F64(1.5));
+        assert_eq!(number_round_trip(Number::F64(0.1)), Number::F64(0.1));
+    }
+
+    /// An array of `n` identically-keyed records — the shape the key
+    /// dictionary exists to compress.
+    fn records<'v>(n: usize) -> Value<'v> {
+        let mut arr = Vec::with_capacity(n);
+        for _ in 0..n {
+            arr.push(obj(vec![
+                ("name", Value::SmallString(small_string(b"alice"))),
+                ("age", Value::Number(Number::I64(30))),
+            ]));
+        }
+        Value::Array(arr)
+    }
+
+    #[test]
+    fn dictionary_mode_round_trips() {
+        let v = records(64);
+        let bytes = to_binary_with(&v, true);
+        assert_ne!(bytes[MAGIC.len()] & FLAG_KEY_DICT, 0);
+        assert_eq!(from_binary(&bytes).unwrap(), v);
+    }
+
+    #[test]
+    fn dictionary_shrinks_repetitive_input() {
+        let v = records(64);
+        assert!(to_binary_with(&v, true).len() < to_binary(&v).len());
+    }
+
+    #[test]
+    fn small_input_falls_back_to_inline_keys() {
+        // One record can't amortize the table, so `to_binary_with` must emit
+        // the same inline-key bytes `to_binary` would.
+        let v = records(1);
+        let dict = to_binary_with(&v, true);
+        assert_eq!(dict[MAGIC.len()] & FLAG_KEY_DICT, 0);
+        assert_eq!(dict, to_binary(&v));
+    }
+
+    #[test]
+    fn oversized_length_does_not_overallocate() {
+        // A tag claiming an array whose length varint is `u64::MAX` must fail
+        // on the missing elements, not abort trying to reserve the capacity.
+        let mut lens = Vec::new();
+        write_varint(&mut lens, u64::MAX);
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC);
+        out.push(0);
+        write_varint(&mut out, 1); // tags
+        write_varint(&mut out, lens.len() as u64);
+        write_varint(&mut out, 0); // nums
+        write_varint(&mut out, 0); // bytes
+        out.push(TAG_ARRAY);
+        out.extend_from_slice(&lens);
+        assert!(from_binary(&out).is_err());
+    }
+}
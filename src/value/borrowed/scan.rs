@@ -0,0 +1,256 @@
+/// Portable small-string scanning for `parse_small_str_`.
+///
+/// The borrowed fast path needs to find the closing quote of a short string
+/// while bailing out as soon as it sees a backslash (an escape forces us back
+/// onto the general `parse_str_` path that de-escapes in place). The original
+/// implementation inlined AVX2 intrinsics, which simply does not build or run
+/// on machines without AVX2 or on aarch64. We factor the scan into a backend
+/// trait with one implementation per instruction set and pick the right one
+/// once at runtime, caching the choice behind an atomic function pointer.
+use super::SMALL_STR_LEN;
+use crate::portability::trailingzeroes;
+use crate::static_cast_u32;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// A backend able to locate the first unescaped `"` in a (padded) input slice.
+///
+/// Implementations return `Some(offset)` for the byte offset of the closing
+/// quote when no backslash precedes it, and `None` when the string is escaped
+/// (or no quote shows up within the scanned window) so the caller falls back
+/// to the general string parser.
+pub(crate) trait SmallStrBackend {
+    /// Scan `src` for the first unescaped quote.
+    ///
+    /// # Safety
+    ///
+    /// `src` must expose at least 64 bytes of readable, padded input: the
+    /// widest backend (AVX2) loads a 32-byte vector at offset 32, reaching
+    /// byte 63. This is the same padding guarantee the deserializer upholds
+    /// for the rest of the SIMD paths.
+    unsafe fn first_quote(src: &[u8]) -> Option<usize>;
+}
+
+/// Pure-scalar fallback: walk the bytes one at a time.
+pub(crate) struct Scalar;
+impl SmallStrBackend for Scalar {
+    unsafe fn first_quote(src: &[u8]) -> Option<usize> {
+        for (i, &b) in src.iter().take(SMALL_STR_LEN + 1).enumerate() {
+            match b {
+                b'\\' => return None,
+                b'"' => return Some(i),
+                _ => (),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) struct Avx2;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl SmallStrBackend for Avx2 {
+    #[target_feature(enable = "avx2")]
+    unsafe fn first_quote(src: &[u8]) -> Option<usize> {
+        let mut offset = 0;
+        while offset <= SMALL_STR_LEN {
+            let chunk = src.get_unchecked(offset..);
+            #[allow(clippy::cast_ptr_alignment)]
+            let v = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+            let bs_bits = static_cast_u32!(_mm256_movemask_epi8(_mm256_cmpeq_epi8(
+                v,
+                _mm256_set1_epi8(b'\\' as i8)
+            )));
+            let quote_bits = static_cast_u32!(_mm256_movemask_epi8(_mm256_cmpeq_epi8(
+                v,
+                _mm256_set1_epi8(b'"' as i8)
+            )));
+            if (bs_bits.wrapping_sub(1) & quote_bits) != 0 {
+                return Some(offset + trailingzeroes(u64::from(quote_bits)) as usize);
+            } else if bs_bits != 0 {
+                return None;
+            }
+            offset += 32;
+        }
+        None
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) struct Sse42;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl SmallStrBackend for Sse42 {
+    #[target_feature(enable = "sse4.2")]
+    unsafe fn first_quote(src: &[u8]) -> Option<usize> {
+        let mut offset = 0;
+        while offset <= SMALL_STR_LEN {
+            let chunk = src.get_unchecked(offset..);
+            #[allow(clippy::cast_ptr_alignment)]
+            let v = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+            let bs_bits = static_cast_u32!(_mm_movemask_epi8(_mm_cmpeq_epi8(
+                v,
+                _mm_set1_epi8(b'\\' as i8)
+            )));
+            let quote_bits = static_cast_u32!(_mm_movemask_epi8(_mm_cmpeq_epi8(
+                v,
+                _mm_set1_epi8(b'"' as i8)
+            )));
+            if (bs_bits.wrapping_sub(1) & quote_bits) != 0 {
+                return Some(offset + trailingzeroes(u64::from(quote_bits)) as usize);
+            } else if bs_bits != 0 {
+                return None;
+            }
+            offset += 16;
+        }
+        None
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub(crate) struct Neon;
+#[cfg(target_arch = "aarch64")]
+impl SmallStrBackend for Neon {
+    #[target_feature(enable = "neon")]
+    unsafe fn first_quote(src: &[u8]) -> Option<usize> {
+        use std::arch::aarch64::*;
+
+        // Collapse a byte-wise comparison result into a 16-bit movemask, the
+        // operation x86 gives us for free via `_mm_movemask_epi8`.
+        #[inline]
+        unsafe fn movemask(input: uint8x16_t) -> u32 {
+            let bit_mask: uint8x16_t = std::mem::transmute([
+                0x01u8, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x01, 0x02, 0x04, 0x08, 0x10,
+                0x20, 0x40, 0x80,
+            ]);
+            let minput = vandq_u8(input, bit_mask);
+            let tmp = vpaddq_u8(minput, minput);
+            let tmp = vpaddq_u8(tmp, tmp);
+            let tmp = vpaddq_u8(tmp, tmp);
+            u32::from(vgetq_lane_u16(vreinterpretq_u16_u8(tmp), 0))
+        }
+
+        let mut offset = 0;
+        while offset <= SMALL_STR_LEN {
+            let chunk = vld1q_u8(src.get_unchecked(offset..).as_ptr());
+            let bs_bits = movemask(vceqq_u8(chunk, vdupq_n_u8(b'\\')));
+            let quote_bits = movemask(vceqq_u8(chunk, vdupq_n_u8(b'"')));
+            if (bs_bits.wrapping_sub(1) & quote_bits) != 0 {
+                return Some(offset + trailingzeroes(u64::from(quote_bits)) as usize);
+            } else if bs_bits != 0 {
+                return None;
+            }
+            offset += 16;
+        }
+        None
+    }
+}
+
+type ScanFn = unsafe fn(&[u8]) -> Option<usize>;
+
+/// Cached, runtime-selected scan implementation. `0` means "not yet resolved".
+static SCAN: AtomicUsize = AtomicUsize::new(0);
+
+/// Pick the best backend the current CPU supports.
+fn detect() -> ScanFn {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return <Avx2 as SmallStrBackend>::first_quote;
+        }
+        if is_x86_feature_detected!("sse4.2") {
+            return <Sse42 as SmallStrBackend>::first_quote;
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            return <Neon as SmallStrBackend>::first_quote;
+        }
+    }
+    <Scalar as SmallStrBackend>::first_quote
+}
+
+/// Offset of the first unescaped closing quote in `src`, or `None` when the
+/// string is escaped and needs the full parser.
+///
+/// # Safety
+///
+/// `src` must uphold the padding guarantee documented on
+/// [`SmallStrBackend::first_quote`].
+#[cfg_attr(not(feature = "no-inline"), inline(always))]
+pub(crate) unsafe fn first_unescaped_quote(src: &[u8]) -> Option<usize> {
+    let mut f = SCAN.load(Ordering::Relaxed);
+    if f == 0 {
+        f = detect() as usize;
+        SCAN.store(f, Ordering::Relaxed);
+    }
+    let f: ScanFn = mem::transmute(f);
+    f(src)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Pad out to a full SIMD window so the backends' unchecked loads stay in
+    /// bounds, matching the guarantee the deserializer gives on real input.
+    fn padded(s: &[u8]) -> Vec<u8> {
+        let mut v = s.to_vec();
+        v.resize(s.len().max(64) + 64, b' ');
+        v
+    }
+
+    #[test]
+    fn simd_backends_match_scalar() {
+        // Every case keeps its quote (if any) inside the inline budget, where
+        // the caller actually consults the result.
+        let mut near_budget = vec![b'a'; 40];
+        near_budget.push(b'"');
+        let cases: Vec<Vec<u8>> = vec![
+            b"\"".to_vec(),
+            b"abc\"".to_vec(),
+            b"no quote within the window".to_vec(),
+            b"esc\\\"aped".to_vec(),
+            b"\\\"".to_vec(),
+            near_budget,
+        ];
+
+        for case in &cases {
+            let buf = padded(case);
+            let expected = unsafe { <Scalar as SmallStrBackend>::first_quote(&buf) };
+
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            {
+                if is_x86_feature_detected!("avx2") {
+                    assert_eq!(
+                        unsafe { <Avx2 as SmallStrBackend>::first_quote(&buf) },
+                        expected
+                    );
+                }
+                if is_x86_feature_detected!("sse4.2") {
+                    assert_eq!(
+                        unsafe { <Sse42 as SmallStrBackend>::first_quote(&buf) },
+                        expected
+                    );
+                }
+            }
+            #[cfg(target_arch = "aarch64")]
+            {
+                if std::arch::is_aarch64_feature_detected!("neon") {
+                    assert_eq!(
+                        unsafe { <Neon as SmallStrBackend>::first_quote(&buf) },
+                        expected
+                    );
+                }
+            }
+
+            // The runtime-dispatched entry point must agree with scalar too.
+            assert_eq!(unsafe { first_unescaped_quote(&buf) }, expected);
+        }
+    }
+}